@@ -109,6 +109,10 @@ pub struct CompileCommand {
     #[arg(long = "ppi", default_value_t = 144.0)]
     pub ppi: f32,
 
+    /// The PDF version to declare for PDF export
+    #[arg(long = "pdf-version", default_value_t = PdfVersion::V1_7)]
+    pub pdf_version: PdfVersion,
+
     /// Produces performance timings of the compilation process (experimental)
     ///
     /// The resulting JSON file can be loaded into a tracing tool such as
@@ -452,3 +456,23 @@ impl Display for OutputFormat {
             .fmt(f)
     }
 }
+
+/// Which PDF version to declare in an exported file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum PdfVersion {
+    #[value(name = "1.5")]
+    V1_5,
+    #[value(name = "1.6")]
+    V1_6,
+    #[value(name = "1.7")]
+    V1_7,
+}
+
+impl Display for PdfVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
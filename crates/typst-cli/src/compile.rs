@@ -15,9 +15,11 @@ use typst::model::Document;
 use typst::syntax::{FileId, Source, Span};
 use typst::visualize::Color;
 use typst::{World, WorldExt};
+use typst_pdf::PdfOptions;
 
 use crate::args::{
     CompileCommand, DiagnosticFormat, Input, Output, OutputFormat, PageRangeArgument,
+    PdfVersion,
 };
 use crate::timings::Timer;
 use crate::watch::Status;
@@ -72,6 +74,15 @@ impl CompileCommand {
             )
         })
     }
+
+    /// The PDF version to declare in an exported PDF.
+    pub fn pdf_version(&self) -> typst_pdf::PdfVersion {
+        match self.pdf_version {
+            PdfVersion::V1_5 => typst_pdf::PdfVersion::Pdf15,
+            PdfVersion::V1_6 => typst_pdf::PdfVersion::Pdf16,
+            PdfVersion::V1_7 => typst_pdf::PdfVersion::Pdf17,
+        }
+    }
 }
 
 /// Execute a compilation command.
@@ -183,8 +194,18 @@ fn export_pdf(document: &Document, command: &CompileCommand) -> StrResult<()> {
     let timestamp = convert_datetime(
         command.common.creation_timestamp.unwrap_or_else(chrono::Utc::now),
     );
-    let exported_page_ranges = command.exported_page_ranges();
-    let buffer = typst_pdf::pdf(document, Smart::Auto, timestamp, exported_page_ranges);
+    let options = PdfOptions {
+        ident: Smart::Auto,
+        timestamp,
+        page_ranges: command.exported_page_ranges(),
+        version: command.pdf_version(),
+        ..PdfOptions::default()
+    };
+    let buffer = typst_pdf::pdf(document, &options).map_err(|errors| {
+        let messages: Vec<_> =
+            errors.iter().map(|error| error.message.as_str()).collect();
+        eco_format!("failed to export PDF ({})", messages.join(", "))
+    })?;
     command
         .output()
         .write(&buffer)
@@ -2,25 +2,26 @@ use std::num::NonZeroUsize;
 
 use ecow::eco_format;
 use pdf_writer::{
-    types::Direction, writers::PageLabel, Finish, Name, Pdf, Ref, Str, TextStr,
+    types::{Direction, OutputIntentSubtype},
+    writers::PageLabel,
+    Filter, Finish, Name, Pdf, Ref, Str, TextStr,
 };
 use xmp_writer::{DateTime, LangId, RenditionClass, Timezone, XmpWriter};
 
+use typst::diag::SourceResult;
 use typst::foundations::{Datetime, Smart};
 use typst::layout::Dir;
 use typst::text::Lang;
 
 use crate::WithEverything;
-use crate::{hash_base64, outline, page::PdfPageLabel};
+use crate::{color, hash_base64, outline, page::PdfPageLabel};
 
 /// Write the document catalog.
 pub fn write_catalog(
     ctx: WithEverything,
-    ident: Smart<&str>,
-    timestamp: Option<Datetime>,
     pdf: &mut Pdf,
     alloc: &mut Ref,
-) {
+) -> SourceResult<()> {
     let lang = ctx
         .resources
         .languages
@@ -49,6 +50,11 @@ pub fn write_catalog(
         xmp.title([(None, title.as_str())]);
     }
 
+    if let Some(description) = &ctx.document.description {
+        info.subject(TextStr(description));
+        xmp.description([(None, description.as_str())]);
+    }
+
     let authors = &ctx.document.author;
     if !authors.is_empty() {
         // Turns out that if the authors are given in both the document
@@ -74,7 +80,9 @@ pub fn write_catalog(
 
     let creator = eco_format!("Typst {}", env!("CARGO_PKG_VERSION"));
     info.creator(TextStr(&creator));
+    info.producer(TextStr(&creator));
     xmp.creator_tool(&creator);
+    xmp.producer(&creator);
 
     let keywords = &ctx.document.keywords;
     if !keywords.is_empty() {
@@ -83,7 +91,7 @@ pub fn write_catalog(
         xmp.pdf_keywords(&joined);
     }
 
-    if let Some(date) = ctx.document.date.unwrap_or(timestamp) {
+    if let Some(date) = ctx.document.date.unwrap_or(ctx.options.timestamp) {
         let tz = ctx.document.date.is_auto();
         if let Some(pdf_date) = pdf_date(date, tz) {
             info.creation_date(pdf_date);
@@ -105,14 +113,14 @@ pub fn write_catalog(
     let instance_id = hash_base64(&pdf.as_bytes());
 
     // Determine the document's ID. It should be as stable as possible.
-    const PDF_VERSION: &str = "PDF-1.7";
-    let doc_id = if let Smart::Custom(ident) = ident {
+    let pdf_version = eco_format!("PDF-{}", ctx.options.version.as_str());
+    let doc_id = if let Smart::Custom(ident) = ctx.options.ident {
         // We were provided with a stable ID. Yay!
-        hash_base64(&(PDF_VERSION, ident))
+        hash_base64(&(pdf_version.as_str(), ident))
     } else if ctx.document.title.is_some() && !ctx.document.author.is_empty() {
         // If not provided from the outside, but title and author were given, we
         // compute a hash of them, which should be reasonably stable and unique.
-        hash_base64(&(PDF_VERSION, &ctx.document.title, &ctx.document.author))
+        hash_base64(&(pdf_version.as_str(), &ctx.document.title, &ctx.document.author))
     } else {
         // The user provided no usable metadata which we can use as an `/ID`.
         instance_id.clone()
@@ -124,7 +132,7 @@ pub fn write_catalog(
     pdf.set_file_id((doc_id.clone().into_bytes(), instance_id.into_bytes()));
 
     xmp.rendition_class(RenditionClass::Proof);
-    xmp.pdf_version("1.7");
+    xmp.pdf_version(ctx.options.version.as_str());
 
     let xmp_buf = xmp.finish(None);
     let meta_ref = alloc.bump();
@@ -132,6 +140,23 @@ pub fn write_catalog(
         .pair(Name(b"Type"), Name(b"Metadata"))
         .pair(Name(b"Subtype"), Name(b"XML"));
 
+    // Write the profile of the output intent, unless the sRGB color space
+    // already embeds it.
+    let output_intent_ref =
+        if ctx.options.output_intent && pdfa_accepts_profile(color::SRGB_ICC) {
+            let srgb_ref = ctx.globals.color_functions.srgb();
+            Some(srgb_ref.unwrap_or_else(|| {
+                let icc_ref = alloc.bump();
+                pdf.icc_profile(icc_ref, &color::SRGB_ICC_DEFLATED)
+                    .n(3)
+                    .range([0.0, 1.0, 0.0, 1.0, 0.0, 1.0])
+                    .filter(Filter::FlateDecode);
+                icc_ref
+            }))
+        } else {
+            None
+        };
+
     // Write the document catalog.
     let catalog_ref = alloc.bump();
     let mut catalog = pdf.catalog(catalog_ref);
@@ -167,7 +192,29 @@ pub fn write_catalog(
         catalog.lang(TextStr(lang.as_str()));
     }
 
+    if let Some(icc_ref) = output_intent_ref {
+        catalog
+            .output_intents()
+            .push()
+            .subtype(OutputIntentSubtype::PDFA)
+            .output_condition_identifier(TextStr("sRGB"))
+            .info(TextStr("sRGB IEC61966-2.1"))
+            .dest_output_profile(icc_ref);
+    }
+
     catalog.finish();
+
+    Ok(())
+}
+
+/// Whether PDF/A accepts an ICC profile as the destination profile of an
+/// output intent.
+///
+/// PDF/A-1 only accepts version 2 profiles. PDF/A-2 and later also accept
+/// version 4 profiles, like the sRGB profile embedded by Typst.
+fn pdfa_accepts_profile(icc: &[u8]) -> bool {
+    // The major version is stored in the ninth byte of the profile header.
+    matches!(icc.get(8), Some(2..=4))
 }
 
 /// Write the page labels.
@@ -276,3 +323,26 @@ fn xmp_date(datetime: Datetime, tz: bool) -> Option<xmp_writer::DateTime> {
         timezone: if tz { Some(Timezone::Utc) } else { None },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use typst::model::Document;
+
+    use crate::{pdf, PdfOptions};
+
+    #[test]
+    fn test_document_info_and_metadata() {
+        let document = Document {
+            title: Some("Report".into()),
+            description: Some("A summary of the document".into()),
+            ..Document::default()
+        };
+
+        let bytes = pdf(&document, &PdfOptions::default()).unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.contains("/Subject (A summary of the document)"));
+        assert!(pdf.contains("/Producer (Typst "));
+        let xmp = &pdf[pdf.find("<dc:description>").unwrap()..];
+        assert!(xmp.contains("A summary of the document</rdf:li>"));
+    }
+}
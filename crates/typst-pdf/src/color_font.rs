@@ -13,16 +13,17 @@ use pdf_writer::Filter;
 use pdf_writer::{types::UnicodeCmap, Finish, Name, Rect, Ref};
 use ttf_parser::name_id;
 
+use typst::diag::SourceResult;
 use typst::layout::Em;
 use typst::text::{color::frame_for_glyph, Font};
 
 use crate::resources::{Resources, ResourcesRefs};
-use crate::WithGlobalRefs;
 use crate::{
     content,
     font::{subset_tag, write_font_descriptor, CMAP_NAME, SYSTEM_INFO},
     EmExt, PdfChunk,
 };
+use crate::{PdfOptions, WithGlobalRefs};
 
 /// Write color fonts in the PDF document.
 ///
@@ -30,12 +31,12 @@ use crate::{
 /// instructions.
 pub fn write_color_fonts(
     context: &WithGlobalRefs,
-) -> (PdfChunk, HashMap<ColorFontSlice, Ref>) {
+) -> SourceResult<(PdfChunk, HashMap<ColorFontSlice, Ref>)> {
     let mut out = HashMap::new();
     let mut chunk = PdfChunk::new();
     context.resources.traverse(&mut |resources: &Resources| {
         let Some(color_fonts) = &resources.color_fonts else {
-            return;
+            return Ok(());
         };
 
         for (color_font, font_slice) in color_fonts.iter() {
@@ -72,12 +73,14 @@ pub fn write_color_fonts(
                     .get() as f32
                     * scale_factor;
                 widths.push(width);
-                chunk
-                    .stream(
-                        instructions_stream_ref,
-                        color_glyph.instructions.content.wait(),
-                    )
-                    .filter(Filter::FlateDecode);
+                let mut stream = chunk.stream(
+                    instructions_stream_ref,
+                    color_glyph.instructions.content.wait(),
+                );
+                if context.options.compress {
+                    stream.filter(Filter::FlateDecode);
+                }
+                stream.finish();
 
                 // Use this stream as instructions to draw the glyph.
                 glyphs_to_instructions.push(instructions_stream_ref);
@@ -148,9 +151,11 @@ pub fn write_color_fonts(
 
             out.insert(font_slice, subfont_id);
         }
-    });
 
-    (chunk, out)
+        Ok(())
+    })?;
+
+    Ok((chunk, out))
 }
 
 /// A mapping between `Font`s and all the corresponding `ColorFont`s.
@@ -210,7 +215,7 @@ impl ColorFontMap<()> {
     ///
     /// If this is the first occurrence of this glyph in this font, it will
     /// start its encoding and add it to the list of known glyphs.
-    pub fn get(&mut self, font: &Font, gid: u16) -> (usize, u8) {
+    pub fn get(&mut self, options: &PdfOptions, font: &Font, gid: u16) -> (usize, u8) {
         let color_font = self.map.entry(font.clone()).or_insert_with(|| {
             let global_bbox = font.ttf().global_bounding_box();
             let bbox = Rect::new(
@@ -243,7 +248,7 @@ impl ColorFontMap<()> {
             let width =
                 font.advance(gid).unwrap_or(Em::new(0.0)).get() * font.units_per_em();
             let instructions =
-                content::build(&mut self.resources, &frame, Some(width as f32));
+                content::build(options, &mut self.resources, &frame, Some(width as f32));
             color_font.glyphs.push(ColorGlyph { gid, instructions });
             color_font.glyph_indices.insert(gid, index);
 
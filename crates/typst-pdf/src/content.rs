@@ -7,7 +7,7 @@
 use ecow::eco_format;
 use pdf_writer::{
     types::{ColorSpaceOperand, LineCapStyle, LineJoinStyle, TextRenderingMode},
-    Content, Finish, Name, Rect, Str,
+    Content, Finish, Name, Rect, Str, TextStr,
 };
 use typst::layout::{
     Abs, Em, Frame, FrameItem, GroupItem, Point, Ratio, Size, Transform,
@@ -21,9 +21,9 @@ use typst::visualize::{
 
 use crate::color_font::ColorFontMap;
 use crate::extg::ExtGState;
-use crate::image::deferred_image;
+use crate::image::{deferred_image, embedded_rotation};
 use crate::{color::PaintEncode, resources::Resources};
-use crate::{deflate_deferred, AbsExt, EmExt};
+use crate::{deflate_deferred, AbsExt, EmExt, PdfOptions};
 
 /// Encode a [`Frame`] into a content stream.
 ///
@@ -34,12 +34,13 @@ use crate::{deflate_deferred, AbsExt, EmExt};
 ///
 /// [color glyph]: `crate::color_font`
 pub fn build(
+    options: &PdfOptions,
     resources: &mut Resources<()>,
     frame: &Frame,
     color_glyph_width: Option<f32>,
 ) -> Encoded {
     let size = frame.size();
-    let mut ctx = Builder::new(resources, size);
+    let mut ctx = Builder::new(options, resources, size);
 
     if let Some(width) = color_glyph_width {
         ctx.content.start_color_glyph(width);
@@ -56,9 +57,14 @@ pub fn build(
     // Encode the frame into the content stream.
     write_frame(&mut ctx, frame);
 
+    let content = ctx.content.finish();
     Encoded {
         size,
-        content: deflate_deferred(ctx.content.finish()),
+        content: if options.compress {
+            deflate_deferred(content)
+        } else {
+            Deferred::new(move || content)
+        },
         uses_opacities: ctx.uses_opacities,
         links: ctx.links,
     }
@@ -68,7 +74,8 @@ pub fn build(
 pub struct Encoded {
     /// The dimensions of the content.
     pub size: Size,
-    /// The actual content stream.
+    /// The actual content stream, compressed if
+    /// [`compress`](PdfOptions::compress) is enabled.
     pub content: Deferred<Vec<u8>>,
     /// Whether the content opacities.
     pub uses_opacities: bool,
@@ -84,6 +91,8 @@ pub struct Encoded {
 /// Content streams can be used for page contents, but also to describe color
 /// glyphs and patterns.
 pub struct Builder<'a, R = ()> {
+    /// Settings for PDF export.
+    pub(crate) options: &'a PdfOptions<'a>,
     /// A list of all resources that are used in the content stream.
     pub(crate) resources: &'a mut Resources<R>,
     /// The PDF content stream that is being built.
@@ -100,8 +109,13 @@ pub struct Builder<'a, R = ()> {
 
 impl<'a, R> Builder<'a, R> {
     /// Create a new content builder.
-    pub fn new(resources: &'a mut Resources<R>, size: Size) -> Self {
+    pub fn new(
+        options: &'a PdfOptions<'a>,
+        resources: &'a mut Resources<R>,
+        size: Size,
+    ) -> Self {
         Builder {
+            options,
             resources,
             uses_opacities: false,
             content: Content::new(),
@@ -460,6 +474,11 @@ fn write_normal_text(ctx: &mut Builder, pos: Point, text: TextItemView) {
     if let Some(stroke) = stroke {
         ctx.set_stroke(stroke, true, fill_transform);
         ctx.set_text_rendering_mode(TextRenderingMode::FillStroke);
+    } else if matches!(&text.item.fill, Paint::Solid(color) if color.alpha() == Some(0.0))
+    {
+        // Fully transparent text isn't painted at all, but stays selectable
+        // and searchable. This is how OCR layers over scanned images work.
+        ctx.set_text_rendering_mode(TextRenderingMode::Invisible);
     } else {
         ctx.set_text_rendering_mode(TextRenderingMode::Fill);
     }
@@ -560,7 +579,7 @@ fn write_color_glyphs(ctx: &mut Builder, pos: Point, text: TextItemView) {
             .resources
             .color_fonts
             .get_or_insert_with(|| Box::new(ColorFontMap::new()));
-        let (font, index) = color_fonts.get(&text.item.font, glyph.id);
+        let (font, index) = color_fonts.get(ctx.options, &text.item.font, glyph.id);
 
         if last_font != Some(font) {
             ctx.content.set_font(
@@ -664,8 +683,8 @@ fn write_path(ctx: &mut Builder, x: f32, y: f32, path: &Path) {
 fn write_image(ctx: &mut Builder, x: f32, y: f32, image: &Image, size: Size) {
     let index = ctx.resources.images.insert(image.clone());
     ctx.resources.deferred_images.entry(index).or_insert_with(|| {
-        let (image, color_space) = deferred_image(image.clone());
-        if let Some(color_space) = color_space {
+        let (image, color_spaces) = deferred_image(image.kind().clone());
+        for color_space in color_spaces {
             ctx.resources.colors.mark_as_used(color_space);
         }
         image
@@ -675,13 +694,25 @@ fn write_image(ctx: &mut Builder, x: f32, y: f32, image: &Image, size: Size) {
     let w = size.x.to_f32();
     let h = size.y.to_f32();
     ctx.content.save_state();
-    ctx.content.transform([w, 0.0, 0.0, -h, x, y + h]);
+
+    // Map the unit square onto the image's area, flipping and rotating the
+    // samples of embedded JPEGs according to their EXIF orientation.
+    ctx.content.transform(match embedded_rotation(image) {
+        Some(2) => [-w, 0.0, 0.0, -h, x + w, y + h],
+        Some(3) => [-w, 0.0, 0.0, h, x + w, y],
+        Some(4) => [w, 0.0, 0.0, h, x, y],
+        Some(5) => [0.0, h, -w, 0.0, x + w, y],
+        Some(6) => [0.0, h, w, 0.0, x, y],
+        Some(7) => [0.0, -h, w, 0.0, x, y + h],
+        Some(8) => [0.0, -h, -w, 0.0, x + w, y + h],
+        _ => [w, 0.0, 0.0, -h, x, y + h],
+    });
 
     if let Some(alt) = image.alt() {
         let mut image_span =
             ctx.content.begin_marked_content_with_properties(Name(b"Span"));
         let mut image_alt = image_span.properties();
-        image_alt.pair(Name(b"Alt"), pdf_writer::Str(alt.as_bytes()));
+        image_alt.pair(Name(b"Alt"), TextStr(alt));
         image_alt.finish();
         image_span.finish();
 
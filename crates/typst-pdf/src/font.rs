@@ -5,11 +5,13 @@ use std::sync::Arc;
 use ecow::{eco_format, EcoString};
 use pdf_writer::{
     types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap},
-    writers::FontDescriptor,
+    writers::{FontDescriptor, Widths},
     Chunk, Filter, Finish, Name, Rect, Ref, Str,
 };
 use subsetter::GlyphRemapper;
 use ttf_parser::{name_id, GlyphId, Tag};
+use typst::diag::{bail, At, SourceResult, StrResult};
+use typst::syntax::Span;
 use typst::text::Font;
 use typst::utils::SliceExt;
 use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
@@ -18,6 +20,7 @@ use crate::{deflate, EmExt, PdfChunk, WithGlobalRefs};
 
 const CFF: Tag = Tag::from_bytes(b"CFF ");
 const CFF2: Tag = Tag::from_bytes(b"CFF2");
+const OS2: Tag = Tag::from_bytes(b"OS/2");
 pub(crate) const CMAP_NAME: Name = Name(b"Custom");
 pub(crate) const SYSTEM_INFO: SystemInfo = SystemInfo {
     registry: Str(b"Adobe"),
@@ -27,7 +30,9 @@ pub(crate) const SYSTEM_INFO: SystemInfo = SystemInfo {
 
 /// Embed all used fonts into the PDF.
 #[typst_macros::time(name = "write fonts")]
-pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
+pub fn write_fonts(
+    context: &WithGlobalRefs,
+) -> SourceResult<(PdfChunk, HashMap<Font, Ref>)> {
     let mut chunk = PdfChunk::new();
     let mut out = HashMap::new();
     context.resources.traverse(&mut |resources| {
@@ -61,6 +66,16 @@ pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
                 .find_name(name_id::POST_SCRIPT_NAME)
                 .unwrap_or_else(|| "unknown".to_string());
 
+            // Fonts cannot be referenced without embedding them, so export
+            // fails if the font's license does not permit it.
+            if let Some(restriction) = embedding_restriction(ttf) {
+                bail!(
+                    Span::detached(),
+                    "failed to embed font {postscript_name}";
+                    hint: "the font's license {restriction}"
+                );
+            }
+
             let subset_tag = subset_tag(glyph_set);
             let base_font = eco_format!("{subset_tag}+{postscript_name}");
             let base_font_type0 = if is_cff {
@@ -83,7 +98,6 @@ pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
             cid.base_font(Name(base_font.as_bytes()));
             cid.system_info(SYSTEM_INFO);
             cid.font_descriptor(descriptor_ref);
-            cid.default_width(0.0);
             if !is_cff {
                 cid.cid_to_gid_map_predefined(Name(b"Identity"));
             }
@@ -99,18 +113,12 @@ pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
                 })
                 .collect::<Vec<_>>();
 
-            // Write all non-zero glyph widths.
-            let mut first = 0;
+            // Write all glyph widths that differ from the most common one,
+            // which becomes the default width.
+            let default_width = most_common_width(&widths);
+            cid.default_width(default_width);
             let mut width_writer = cid.widths();
-            for (w, group) in widths.group_by_key(|&w| w) {
-                let end = first + group.len();
-                if w != 0.0 {
-                    let last = end - 1;
-                    width_writer.same(first as u16, last as u16, w);
-                }
-                first = end;
-            }
-
+            write_widths(&mut width_writer, &widths, default_width);
             width_writer.finish();
             cid.finish();
 
@@ -119,11 +127,15 @@ pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
             let cmap = create_cmap(glyph_set, glyph_remapper);
             chunk.cmap(cmap_ref, &cmap).filter(Filter::FlateDecode);
 
-            let subset = subset_font(font, glyph_remapper);
+            let (subset, length) =
+                subset_font(font, glyph_remapper).at(Span::detached())?;
             let mut stream = chunk.stream(data_ref, &subset);
             stream.filter(Filter::FlateDecode);
             if is_cff {
                 stream.pair(Name(b"Subtype"), Name(b"CIDFontType0C"));
+            } else {
+                // TrueType font programs must declare their decoded length.
+                stream.pair(Name(b"Length1"), length as i32);
             }
             stream.finish();
 
@@ -135,9 +147,77 @@ pub fn write_fonts(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Font, Ref>) {
                 font_descriptor.font_file2(data_ref);
             }
         }
-    });
 
-    (chunk, out)
+        Ok(())
+    })?;
+
+    Ok((chunk, out))
+}
+
+/// Check whether the `fsType` flags of a font's OS/2 table permit embedding a
+/// subset of its outlines, and describe the restriction otherwise.
+fn embedding_restriction(ttf: &ttf_parser::Face) -> Option<&'static str> {
+    let fs_type = ttf
+        .raw_face()
+        .table(OS2)
+        .and_then(|os2| os2.get(8..10))
+        .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+    // Of the usage permissions, only "restricted license" forbids embedding.
+    // If several are set, the least restrictive one applies.
+    if fs_type & 0x000F == 0x0002 {
+        Some("does not allow embedding")
+    } else if fs_type & 0x0200 != 0 {
+        Some("only allows embedding bitmaps")
+    } else if fs_type & 0x0100 != 0 {
+        Some("does not allow subsetting")
+    } else {
+        None
+    }
+}
+
+/// Find the width shared by the most glyphs, or zero if there are none.
+fn most_common_width(widths: &[f32]) -> f32 {
+    let mut sorted = widths.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    sorted
+        .group_by_key(|&w| w)
+        .max_by_key(|(_, group)| group.len())
+        .map_or(0.0, |(w, _)| w)
+}
+
+/// Write the widths of consecutive CIDs, starting at zero, and skip those
+/// equal to the default width.
+///
+/// Runs of at least three equal widths are written as a single range. All
+/// other widths are batched into arrays of consecutive widths, which is more
+/// compact than a range per glyph.
+fn write_widths(writer: &mut Widths, widths: &[f32], default: f32) {
+    let mut first = 0;
+    let mut batch_start = 0;
+    let mut batch = vec![];
+    for (w, group) in widths.group_by_key(|&w| w) {
+        let end = first + group.len();
+        if w != default && group.len() < 3 {
+            if batch.is_empty() {
+                batch_start = first;
+            }
+            batch.extend_from_slice(group);
+        } else {
+            if !batch.is_empty() {
+                writer.consecutive(batch_start as u16, batch.drain(..));
+            }
+            if w != default {
+                let last = end - 1;
+                writer.same(first as u16, last as u16, w);
+            }
+        }
+        first = end;
+    }
+
+    if !batch.is_empty() {
+        writer.consecutive(batch_start as u16, batch);
+    }
 }
 
 /// Writes a FontDescriptor dictionary.
@@ -153,8 +233,19 @@ pub fn write_font_descriptor<'a>(
         .find_name(name_id::POST_SCRIPT_NAME)
         .unwrap_or_else(|| "unknown".to_string());
 
+    // Classify the font through its PANOSE family and serif style, falling
+    // back to its name when the classification is missing or unspecific.
+    let panose = ttf.raw_face().table(OS2).and_then(|os2| os2.get(32..34));
+    let (serif, script) = match panose {
+        Some(&[2, 2..=10]) => (true, false),
+        Some(&[2, 11..=15]) => (false, false),
+        Some(&[3, _]) => (false, true),
+        _ => (postscript_name.contains("Serif"), false),
+    };
+
     let mut flags = FontFlags::empty();
-    flags.set(FontFlags::SERIF, postscript_name.contains("Serif"));
+    flags.set(FontFlags::SERIF, serif);
+    flags.set(FontFlags::SCRIPT, script);
     flags.set(FontFlags::FIXED_PITCH, ttf.is_monospaced());
     flags.set(FontFlags::ITALIC, ttf.is_italic());
     flags.insert(FontFlags::SYMBOLIC);
@@ -194,13 +285,21 @@ pub fn write_font_descriptor<'a>(
 /// - For a font with TrueType outlines, this produces the whole OpenType font.
 /// - For a font with CFF outlines, this produces just the CFF font program.
 ///
-/// In both cases, this returns the already compressed data.
+/// In both cases, this returns the already compressed data along with its
+/// uncompressed length, or an error if the subsetter failed.
 #[comemo::memoize]
 #[typst_macros::time(name = "subset font")]
-fn subset_font(font: &Font, glyph_remapper: &GlyphRemapper) -> Arc<Vec<u8>> {
+fn subset_font(
+    font: &Font,
+    glyph_remapper: &GlyphRemapper,
+) -> StrResult<(Arc<Vec<u8>>, usize)> {
     let data = font.data();
-    // TODO: Fail export instead of unwrapping once export diagnoistics exist.
-    let subsetted = subsetter::subset(data, font.index(), glyph_remapper).unwrap();
+    let subsetted =
+        subsetter::subset(data, font.index(), glyph_remapper).map_err(|err| {
+            let postscript_name = font.find_name(name_id::POST_SCRIPT_NAME);
+            let name = postscript_name.as_deref().unwrap_or(&font.info().family);
+            eco_format!("failed to subset font {name} ({err})")
+        })?;
 
     let mut data = subsetted.as_ref();
 
@@ -210,7 +309,7 @@ fn subset_font(font: &Font, glyph_remapper: &GlyphRemapper) -> Arc<Vec<u8>> {
         data = cff;
     }
 
-    Arc::new(deflate(data))
+    Ok((Arc::new(deflate(data)), data.len()))
 }
 
 /// Produce a unique 6 letter tag for a glyph set.
@@ -276,3 +375,26 @@ fn create_cmap(
     }
     Arc::new(deflate(&cmap.finish()))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use subsetter::GlyphRemapper;
+
+    use super::create_cmap;
+
+    #[test]
+    fn test_cmap_astral_plane() {
+        let mut glyph_remapper = GlyphRemapper::new();
+        let cid = glyph_remapper.remap(5);
+        let glyph_set = BTreeMap::from([(5, "😀".into())]);
+
+        let deflated = create_cmap(&glyph_set, &glyph_remapper);
+        let cmap = miniz_oxide::inflate::decompress_to_vec_zlib(&deflated).unwrap();
+        let cmap = String::from_utf8(cmap).unwrap().to_ascii_uppercase();
+
+        // U+1F600 must be encoded as a UTF-16BE surrogate pair.
+        assert!(cmap.contains(&format!("<{cid:04X}> <D83DDE00>")));
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 
-use image::{DynamicImage, GenericImageView, Rgba};
-use pdf_writer::{Chunk, Filter, Finish, Ref};
+use ecow::eco_format;
+use image::{ColorType, DynamicImage, GenericImageView, Rgb, RgbImage, Rgba};
+use pdf_writer::{Chunk, Filter, Finish, Name, Ref, Str};
+use typst::diag::{At, SourceResult, StrResult};
+use typst::syntax::Span;
 use typst::utils::Deferred;
 use typst::visualize::{
     ColorSpace, Image, ImageKind, RasterFormat, RasterImage, SvgImage,
@@ -12,21 +14,37 @@ use crate::{color, deflate, PdfChunk, WithGlobalRefs};
 
 /// Embed all used images into the PDF.
 #[typst_macros::time(name = "write images")]
-pub fn write_images(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Image, Ref>) {
+pub fn write_images(
+    context: &WithGlobalRefs,
+) -> SourceResult<(PdfChunk, HashMap<Image, Ref>)> {
     let mut chunk = PdfChunk::new();
     let mut out = HashMap::new();
+    // Images that only differ in their alternative description share a
+    // single XObject since the description lives in the content stream.
+    let mut by_content = HashMap::new();
     context.resources.traverse(&mut |resources| {
         for (i, image) in resources.images.items().enumerate() {
             if out.contains_key(image) {
                 continue;
             }
 
+            let hash = typst::utils::hash128(image.kind());
+            if let Some(&image_ref) = by_content.get(&hash) {
+                out.insert(image.clone(), image_ref);
+                continue;
+            }
+
             let handle = resources.deferred_images.get(&i).unwrap();
-            match handle.wait() {
+            let encoded =
+                handle.wait().as_ref().map_err(Clone::clone).at(Span::detached())?;
+            match encoded {
                 EncodedImage::Raster {
                     data,
                     filter,
-                    has_color,
+                    color,
+                    inverted,
+                    palette,
+                    bits_per_component,
                     width,
                     height,
                     icc,
@@ -34,46 +52,62 @@ pub fn write_images(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Image, Ref>)
                 } => {
                     let image_ref = chunk.alloc();
                     out.insert(image.clone(), image_ref);
+                    by_content.insert(hash, image_ref);
 
                     let mut image = chunk.chunk.image_xobject(image_ref, data);
                     image.filter(*filter);
                     image.width(*width as i32);
                     image.height(*height as i32);
-                    image.bits_per_component(8);
+                    image.bits_per_component(*bits_per_component);
+                    if context.options.interpolate_images {
+                        image.interpolate(true);
+                    }
+                    if *inverted {
+                        image.decode(color.inverted_decode());
+                    }
 
                     let mut icc_ref = None;
-                    let space = image.color_space();
-                    if icc.is_some() {
-                        let id = chunk.alloc.bump();
-                        space.icc_based(id);
-                        icc_ref = Some(id);
-                    } else if *has_color {
+                    if let Some(palette) = palette {
+                        // Each sample is an index into the palette, which
+                        // holds colors in the base color space.
+                        let mut space = image.insert(Name(b"ColorSpace")).array();
+                        space.item(Name(b"Indexed"));
                         color::write(
                             ColorSpace::Srgb,
-                            space,
+                            space.push().start(),
                             &context.globals.color_functions,
                         );
-                    } else {
+                        space.item((palette.len() / 3) as i32 - 1);
+                        space.item(Str(palette));
+                        space.finish();
+                    } else if icc.is_some() {
+                        let space = image.color_space();
+                        let id = chunk.alloc.bump();
+                        space.icc_based(id);
+                        icc_ref = Some(id);
+                    } else if let Some(space) = color.color_space() {
                         color::write(
-                            ColorSpace::D65Gray,
                             space,
+                            image.color_space(),
                             &context.globals.color_functions,
                         );
+                    } else {
+                        image.color_space().device_cmyk();
                     }
 
                     // Add a second gray-scale image containing the alpha values if
                     // this image has an alpha channel.
-                    if let Some((alpha_data, alpha_filter)) = alpha {
+                    if let Some(alpha) = alpha {
                         let mask_ref = chunk.alloc.bump();
                         image.s_mask(mask_ref);
                         image.finish();
 
-                        let mut mask = chunk.image_xobject(mask_ref, alpha_data);
-                        mask.filter(*alpha_filter);
+                        let mut mask = chunk.image_xobject(mask_ref, &alpha.data);
+                        mask.filter(alpha.filter);
                         mask.width(*width as i32);
                         mask.height(*height as i32);
                         mask.color_space().device_gray();
-                        mask.bits_per_component(8);
+                        mask.bits_per_component(alpha.bits_per_component);
                     } else {
                         image.finish();
                     }
@@ -81,12 +115,17 @@ pub fn write_images(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Image, Ref>)
                     if let (Some(icc), Some(icc_ref)) = (icc, icc_ref) {
                         let mut stream = chunk.icc_profile(icc_ref, icc);
                         stream.filter(Filter::FlateDecode);
-                        if *has_color {
-                            stream.n(3);
-                            stream.alternate().srgb();
-                        } else {
-                            stream.n(1);
-                            stream.alternate().d65_gray();
+                        stream.n(color.channel_count());
+                        match color {
+                            ImageColor::Gray => {
+                                stream.alternate().d65_gray();
+                            }
+                            ImageColor::Rgb => {
+                                stream.alternate().srgb();
+                            }
+                            ImageColor::Cmyk => {
+                                stream.alternate().device_cmyk();
+                            }
                         }
                     }
                 }
@@ -96,95 +135,375 @@ pub fn write_images(context: &WithGlobalRefs) -> (PdfChunk, HashMap<Image, Ref>)
                         *map.entry(old).or_insert_with(|| chunk.alloc.bump())
                     });
                     out.insert(image.clone(), map[&id]);
+                    by_content.insert(hash, map[&id]);
                 }
             }
         }
-    });
 
-    (chunk, out)
+        Ok(())
+    })?;
+
+    Ok((chunk, out))
 }
 
-/// Creates a new PDF image from the given image.
+/// Creates a new PDF image from the given image data.
 ///
-/// Also starts the deferred encoding of the image.
+/// Also starts the deferred encoding of the image. This is memoized on the
+/// data only, so that images which just differ in their alternative
+/// description (which is written into the content stream) are encoded once.
+///
+/// Returns the color spaces that the encoded image may use.
 #[comemo::memoize]
-pub fn deferred_image(image: Image) -> (Deferred<EncodedImage>, Option<ColorSpace>) {
-    let color_space = match image.kind() {
-        ImageKind::Raster(raster) if raster.icc().is_none() => {
-            if raster.dynamic().color().channel_count() > 2 {
-                Some(ColorSpace::Srgb)
-            } else {
-                Some(ColorSpace::D65Gray)
-            }
-        }
-        _ => None,
+pub fn deferred_image(
+    kind: ImageKind,
+) -> (Deferred<StrResult<EncodedImage>>, Vec<ColorSpace>) {
+    let color = match &kind {
+        ImageKind::Raster(raster) => image_color(raster),
+        ImageKind::Svg(_) => ImageColor::Rgb,
     };
 
-    let deferred = Deferred::new(move || match image.kind() {
-        ImageKind::Raster(raster) => {
-            let raster = raster.clone();
-            let (width, height) = (raster.width(), raster.height());
-            let (data, filter, has_color) = encode_raster_image(&raster);
-            let icc = raster.icc().map(deflate);
+    // Whether a color image only has gray pixels is just known once it is
+    // encoded, so it reserves the gray color space as well.
+    let grayable = match &kind {
+        ImageKind::Raster(raster) => may_be_gray(raster, color),
+        ImageKind::Svg(_) => false,
+    };
 
-            let alpha =
-                raster.dynamic().color().has_alpha().then(|| encode_alpha(&raster));
+    let mut color_spaces = vec![];
+    if matches!(&kind, ImageKind::Raster(raster) if raster.icc().is_none()) {
+        color_spaces.extend(color.color_space());
+        if grayable {
+            color_spaces.push(ColorSpace::D65Gray);
+        }
+    }
 
-            EncodedImage::Raster { data, filter, has_color, width, height, icc, alpha }
+    let deferred = Deferred::new(move || match &kind {
+        ImageKind::Raster(raster) => {
+            let color =
+                if grayable && is_gray(raster) { ImageColor::Gray } else { color };
+            Ok(encode_raster_image(raster, color, is_16_bit(raster.dynamic())))
         }
         ImageKind::Svg(svg) => {
-            let (chunk, id) = encode_svg(svg);
-            EncodedImage::Svg(chunk, id)
+            let (chunk, id) = encode_svg(svg)?;
+            Ok(EncodedImage::Svg(chunk, id))
         }
     });
 
-    (deferred, color_space)
+    (deferred, color_spaces)
+}
+
+/// Determine the color model of a raster image's channels.
+///
+/// Embedded JPEGs keep the channels of the original file.
+fn image_color(image: &RasterImage) -> ImageColor {
+    if let Some(header) = embeddable_jpeg(image) {
+        return match header.components {
+            1 => ImageColor::Gray,
+            4 => ImageColor::Cmyk,
+            _ => ImageColor::Rgb,
+        };
+    }
+
+    if image.dynamic().color().channel_count() <= 2 {
+        ImageColor::Gray
+    } else {
+        ImageColor::Rgb
+    }
+}
+
+/// Whether a color image is stored in a single channel if all of its pixels
+/// are gray.
+///
+/// This is not the case for embedded JPEGs (whose channels are fixed by the
+/// original file) and images with an ICC profile (which describes the
+/// existing channels).
+fn may_be_gray(image: &RasterImage, color: ImageColor) -> bool {
+    color == ImageColor::Rgb && embeddable_jpeg(image).is_none() && image.icc().is_none()
+}
+
+/// Whether all pixels of an image are gray.
+#[typst_macros::time(name = "detect gray image")]
+fn is_gray(image: &RasterImage) -> bool {
+    let dynamic = image.dynamic();
+    match dynamic {
+        // Compare 16-bit images at full precision.
+        DynamicImage::ImageRgb16(rgb) => {
+            rgb.pixels().all(|Rgb([r, g, b])| r == g && g == b)
+        }
+        DynamicImage::ImageRgba16(rgba) => {
+            rgba.pixels().all(|Rgba([r, g, b, _])| r == g && g == b)
+        }
+        _ => dynamic.pixels().all(|(_, _, Rgba([r, g, b, _]))| r == g && g == b),
+    }
 }
 
-/// Encode an image with a suitable filter and return the data, filter and
-/// whether the image has color.
+/// Encode a raster image with a suitable filter, in the given color model.
 ///
-/// Skips the alpha channel as that's encoded separately.
+/// If `deep` is set, the samples are stored with 16 bits instead of 8. The
+/// alpha channel is encoded into a separate soft mask.
 #[typst_macros::time(name = "encode raster image")]
-fn encode_raster_image(image: &RasterImage) -> (Vec<u8>, Filter, bool) {
+fn encode_raster_image(
+    image: &RasterImage,
+    color: ImageColor,
+    deep: bool,
+) -> EncodedImage {
     let dynamic = image.dynamic();
-    let channel_count = dynamic.color().channel_count();
-    let has_color = channel_count > 2;
+    let mut palette = None;
+    let mut bits_per_component = 8;
+    let (mut width, mut height) = (image.width(), image.height());
 
-    if image.format() == RasterFormat::Jpg {
-        let mut data = Cursor::new(vec![]);
-        dynamic.write_to(&mut data, image::ImageFormat::Jpeg).unwrap();
-        (data.into_inner(), Filter::DctDecode, has_color)
+    let (data, filter) = if embeddable_jpeg(image).is_some() {
+        // Embed the original file, which avoids a lossy re-encoding. Its
+        // samples are stored without the EXIF orientation, which is applied
+        // when the image is drawn instead. All other JPEGs are stored
+        // losslessly from their decoded pixels below.
+        if matches!(image.rotation(), Some(5..=8)) {
+            std::mem::swap(&mut width, &mut height);
+        }
+        (image.data().to_vec(), Filter::DctDecode)
+    } else if deep {
+        // Keep the full precision, with big-endian samples.
+        bits_per_component = 16;
+        let samples = if color == ImageColor::Gray {
+            dynamic.to_luma16().into_raw()
+        } else {
+            dynamic.to_rgb16().into_raw()
+        };
+        let bytes: Vec<u8> = samples.into_iter().flat_map(u16::to_be_bytes).collect();
+        (deflate(&bytes), Filter::FlateDecode)
     } else {
         // TODO: Encode flate streams with PNG-predictor?
-        let data = match (dynamic, channel_count) {
-            (DynamicImage::ImageLuma8(luma), _) => deflate(luma.as_raw()),
-            (DynamicImage::ImageRgb8(rgb), _) => deflate(rgb.as_raw()),
+        let has_color = color != ImageColor::Gray;
+        let data = match (dynamic, has_color) {
             // Grayscale image
-            (_, 1 | 2) => deflate(dynamic.to_luma8().as_raw()),
-            // Anything else
-            _ => deflate(dynamic.to_rgb8().as_raw()),
+            (_, false) => {
+                let converted;
+                let luma = match dynamic {
+                    DynamicImage::ImageLuma8(luma) => luma,
+                    _ => {
+                        converted = dynamic.to_luma8();
+                        &converted
+                    }
+                };
+                match pack_bilevel(luma.as_raw(), luma.width(), u8::MAX) {
+                    Some(bits) => {
+                        bits_per_component = 1;
+                        deflate(&bits)
+                    }
+                    None => deflate(luma.as_raw()),
+                }
+            }
+            // Color image
+            (_, true) => {
+                let converted;
+                let rgb = match dynamic {
+                    DynamicImage::ImageRgb8(rgb) => rgb,
+                    _ => {
+                        converted = dynamic.to_rgb8();
+                        &converted
+                    }
+                };
+                // Use a palette for images with few colors, unless the ICC
+                // profile must be kept.
+                let palettized =
+                    if image.icc().is_none() { palettize(rgb) } else { None };
+                match palettized {
+                    Some((colors, indices)) => {
+                        palette = Some(colors);
+                        deflate(&indices)
+                    }
+                    None => deflate(rgb.as_raw()),
+                }
+            }
         };
-        (data, Filter::FlateDecode, has_color)
+        (data, Filter::FlateDecode)
+    };
+
+    let inverted = color == ImageColor::Cmyk
+        && parse_jpeg_header(image.data()).is_some_and(|header| header.adobe);
+    let icc = image.icc().map(deflate);
+    let alpha = dynamic.color().has_alpha().then(|| encode_alpha(image, deep));
+
+    EncodedImage::Raster {
+        data,
+        filter,
+        color,
+        inverted,
+        palette,
+        bits_per_component,
+        width,
+        height,
+        icc,
+        alpha,
+    }
+}
+
+/// Whether an image has 16 bits per channel.
+fn is_16_bit(image: &DynamicImage) -> bool {
+    matches!(
+        image.color(),
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16
+    )
+}
+
+/// Try to pack single-channel samples that are all either zero or `max` into
+/// one bit per sample.
+///
+/// The samples are given row by row, `width` per row. Each packed row starts
+/// at a new byte, as required for PDF image samples.
+fn pack_bilevel<T>(samples: &[T], width: u32, max: T) -> Option<Vec<u8>>
+where
+    T: Copy + Default + PartialEq,
+{
+    let width = width as usize;
+    let row_len = width.div_ceil(8);
+    let height = samples.len().checked_div(width).unwrap_or(0);
+    let mut bits = vec![0; row_len * height];
+    for (i, &v) in samples.iter().enumerate() {
+        if v == max {
+            let (y, x) = (i / width, i % width);
+            bits[y * row_len + x / 8] |= 0x80 >> (x % 8);
+        } else if v != T::default() {
+            return None;
+        }
+    }
+    Some(bits)
+}
+
+/// Try to express an image through a palette of at most 256 colors.
+///
+/// Returns the palette as consecutive RGB triplets and the palette index of
+/// each pixel.
+fn palettize(image: &RgbImage) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut lookup = HashMap::new();
+    let mut palette = vec![];
+    let mut indices =
+        Vec::with_capacity(image.width() as usize * image.height() as usize);
+    for &Rgb(color) in image.pixels() {
+        let next = lookup.len();
+        let index = *lookup.entry(color).or_insert(next);
+        if index == next {
+            if next > u8::MAX as usize {
+                return None;
+            }
+            palette.extend(color);
+        }
+        indices.push(index as u8);
+    }
+    Some((palette, indices))
+}
+
+/// Details from the headers of a JPEG file.
+struct JpegHeader {
+    /// The number of color components.
+    components: u8,
+    /// Whether the file was written by an Adobe application, which store
+    /// CMYK samples inverted.
+    adobe: bool,
+    /// Whether PDF readers can decode the file with the DCTDecode filter,
+    /// that is, whether it is a baseline, extended or progressive
+    /// Huffman-coded JPEG with 8-bit samples.
+    dct_decodable: bool,
+}
+
+/// Parse the headers of a JPEG image that can be embedded as is.
+fn embeddable_jpeg(image: &RasterImage) -> Option<JpegHeader> {
+    if image.format() != RasterFormat::Jpg {
+        return None;
+    }
+    parse_jpeg_header(image.data())
+        .filter(|header| header.dct_decodable && matches!(header.components, 1 | 3 | 4))
+}
+
+/// The EXIF orientation by which an image must be transformed when it is
+/// drawn, because it embeds the original data of a rotated JPEG.
+pub fn embedded_rotation(image: &Image) -> Option<u32> {
+    match image.kind() {
+        ImageKind::Raster(raster) if embeddable_jpeg(raster).is_some() => {
+            raster.rotation()
+        }
+        _ => None,
+    }
+}
+
+/// Read the headers of a JPEG file up to its frame header.
+fn parse_jpeg_header(data: &[u8]) -> Option<JpegHeader> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    // Skip over all marker segments until the start of frame is found.
+    let mut i = 2;
+    let mut adobe = false;
+    while i + 4 <= data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+
+        let marker = data[i + 1];
+        if marker == 0xFF {
+            // Fill byte.
+            i += 1;
+            continue;
+        }
+
+        // The APP14 segment that Adobe applications write.
+        if marker == 0xEE && data.get(i + 4..i + 9) == Some(b"Adobe".as_slice()) {
+            adobe = true;
+        }
+
+        // SOF markers, except for DHT, JPG and DAC which share their range.
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let precision = *data.get(i + 4)?;
+            let components = *data.get(i + 9)?;
+            let dct_decodable = matches!(marker, 0xC0..=0xC2) && precision == 8;
+            return Some(JpegHeader { components, adobe, dct_decodable });
+        }
+
+        let len = u16::from_be_bytes([data[i + 2], data[i + 3]]);
+        i += 2 + usize::from(len);
     }
+
+    None
 }
 
-/// Encode an image's alpha channel if present.
+/// Encode an image's alpha channel if present, with 16 bits if `deep` is set.
 #[typst_macros::time(name = "encode alpha")]
-fn encode_alpha(raster: &RasterImage) -> (Vec<u8>, Filter) {
-    let pixels: Vec<_> = raster
-        .dynamic()
-        .pixels()
-        .map(|(_, _, Rgba([_, _, _, a]))| a)
-        .collect();
-    (deflate(&pixels), Filter::FlateDecode)
+fn encode_alpha(raster: &RasterImage, deep: bool) -> EncodedMask {
+    let dynamic = raster.dynamic();
+    let width = dynamic.width();
+
+    // Keyed transparency, where each pixel is either fully opaque or fully
+    // transparent, only needs a single bit per pixel. Otherwise, the mask
+    // keeps the depth of the image.
+    let (samples, bits_per_component) = if deep {
+        let alpha: Vec<u16> =
+            dynamic.to_rgba16().pixels().map(|&Rgba([_, _, _, a])| a).collect();
+        match pack_bilevel(&alpha, width, u16::MAX) {
+            Some(bits) => (bits, 1),
+            None => (alpha.into_iter().flat_map(u16::to_be_bytes).collect(), 16),
+        }
+    } else {
+        let alpha: Vec<u8> =
+            dynamic.pixels().map(|(_, _, Rgba([_, _, _, a]))| a).collect();
+        match pack_bilevel(&alpha, width, u8::MAX) {
+            Some(bits) => (bits, 1),
+            None => (alpha, 8),
+        }
+    };
+
+    EncodedMask {
+        data: deflate(&samples),
+        filter: Filter::FlateDecode,
+        bits_per_component,
+    }
 }
 
 /// Encode an SVG into a chunk of PDF objects.
 #[typst_macros::time(name = "encode svg")]
-fn encode_svg(svg: &SvgImage) -> (Chunk, Ref) {
-    // TODO: Don't unwrap once we have export diagostics.
-    svg2pdf::to_chunk(svg.tree(), svg2pdf::ConversionOptions::default()).unwrap()
+fn encode_svg(svg: &SvgImage) -> StrResult<(Chunk, Ref)> {
+    svg2pdf::to_chunk(svg.tree(), svg2pdf::ConversionOptions::default())
+        .map_err(|err| eco_format!("failed to convert SVG to PDF ({err})"))
 }
 
 /// A pre-encoded image.
@@ -195,8 +514,15 @@ pub enum EncodedImage {
         data: Vec<u8>,
         /// The filter to use for the image.
         filter: Filter,
-        /// Whether the image has color.
-        has_color: bool,
+        /// The color model of the image's samples.
+        color: ImageColor,
+        /// Whether the samples are stored inverted.
+        inverted: bool,
+        /// The RGB color lookup table if the data consists of indices into
+        /// it.
+        palette: Option<Vec<u8>>,
+        /// The number of bits per sample.
+        bits_per_component: i32,
         /// The image's width.
         width: u32,
         /// The image's height.
@@ -204,10 +530,57 @@ pub enum EncodedImage {
         /// The image's ICC profile, pre-deflated, if any.
         icc: Option<Vec<u8>>,
         /// The alpha channel of the image, pre-deflated, if any.
-        alpha: Option<(Vec<u8>, Filter)>,
+        alpha: Option<EncodedMask>,
     },
     /// A vector graphic.
     ///
     /// The chunk is the SVG converted to PDF objects.
     Svg(Chunk, Ref),
 }
+
+/// The alpha channel of a raster image, encoded as a soft mask.
+pub struct EncodedMask {
+    /// The raw, pre-deflated mask data.
+    data: Vec<u8>,
+    /// The filter to use for the mask.
+    filter: Filter,
+    /// The number of bits per sample.
+    bits_per_component: i32,
+}
+
+/// The color model of a raster image's samples.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ImageColor {
+    /// A single gray channel.
+    Gray,
+    /// Red, green and blue channels.
+    Rgb,
+    /// Cyan, magenta, yellow and key channels.
+    Cmyk,
+}
+
+impl ImageColor {
+    /// The number of channels per pixel.
+    pub fn channel_count(self) -> i32 {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb => 3,
+            Self::Cmyk => 4,
+        }
+    }
+
+    /// The calibrated color space in which the samples are interpreted, if
+    /// any. CMYK samples are device-dependent.
+    pub fn color_space(self) -> Option<ColorSpace> {
+        match self {
+            Self::Gray => Some(ColorSpace::D65Gray),
+            Self::Rgb => Some(ColorSpace::Srgb),
+            Self::Cmyk => None,
+        }
+    }
+
+    /// A decode array that maps inverted samples to their actual values.
+    fn inverted_decode(self) -> Vec<f32> {
+        [1.0, 0.0].repeat(self.channel_count() as usize)
+    }
+}
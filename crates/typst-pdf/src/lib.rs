@@ -20,6 +20,7 @@ use std::ops::{Deref, DerefMut};
 
 use base64::Engine;
 use pdf_writer::{Chunk, Pdf, Ref};
+use typst::diag::SourceResult;
 use typst::foundations::{Datetime, Smart};
 use typst::layout::{Abs, Em, PageRanges, Transform};
 use typst::model::Document;
@@ -43,52 +44,129 @@ use crate::resources::{
 
 /// Export a document into a PDF file.
 ///
-/// Returns the raw bytes making up the PDF file.
-///
-/// The `ident` parameter, if given, shall be a string that uniquely and stably
-/// identifies the document. It should not change between compilations of the
-/// same document.  **If you cannot provide such a stable identifier, just pass
-/// `Smart::Auto` rather than trying to come up with one.** The CLI, for
-/// example, does not have a well-defined notion of a long-lived project and as
-/// such just passes `Smart::Auto`.
-///
-/// If an `ident` is given, the hash of it will be used to create a PDF document
-/// identifier (the identifier itself is not leaked). If `ident` is `Auto`, a
-/// hash of the document's title and author is used instead (which is reasonably
-/// unique and stable).
-///
-/// The `timestamp`, if given, is expected to be the creation date of the
-/// document as a UTC datetime. It will only be used if `set document(date: ..)`
-/// is `auto`.
-///
-/// The `page_ranges` option specifies which ranges of pages should be exported
-/// in the PDF. When `None`, all pages should be exported.
+/// Returns the raw bytes making up the PDF file, or the errors that occurred
+/// while embedding the document's resources, e.g. a font that could not be
+/// subsetted.
 #[typst_macros::time(name = "pdf")]
-pub fn pdf(
-    document: &Document,
-    ident: Smart<&str>,
-    timestamp: Option<Datetime>,
-    page_ranges: Option<PageRanges>,
-) -> Vec<u8> {
-    PdfBuilder::new(document, page_ranges)
-        .phase(|builder| builder.run(traverse_pages))
-        .phase(|builder| GlobalRefs {
-            color_functions: builder.run(alloc_color_functions_refs),
-            pages: builder.run(alloc_page_refs),
-            resources: builder.run(alloc_resources_refs),
-        })
-        .phase(|builder| References {
-            named_destinations: builder.run(write_named_destinations),
-            fonts: builder.run(write_fonts),
-            color_fonts: builder.run(write_color_fonts),
-            images: builder.run(write_images),
-            gradients: builder.run(write_gradients),
-            patterns: builder.run(write_patterns),
-            ext_gs: builder.run(write_graphic_states),
-        })
-        .phase(|builder| builder.run(write_page_tree))
-        .phase(|builder| builder.run(write_resource_dictionaries))
-        .export_with(ident, timestamp, write_catalog)
+pub fn pdf(document: &Document, options: &PdfOptions) -> SourceResult<Vec<u8>> {
+    PdfBuilder::new(document, options)
+        .phase(|builder| builder.run(traverse_pages))?
+        .phase(|builder| {
+            Ok(GlobalRefs {
+                color_functions: builder.run(alloc_color_functions_refs)?,
+                pages: builder.run(alloc_page_refs)?,
+                resources: builder.run(alloc_resources_refs)?,
+            })
+        })?
+        .phase(|builder| {
+            Ok(References {
+                named_destinations: builder.run(write_named_destinations)?,
+                fonts: builder.run(write_fonts)?,
+                color_fonts: builder.run(write_color_fonts)?,
+                images: builder.run(write_images)?,
+                gradients: builder.run(write_gradients)?,
+                patterns: builder.run(write_patterns)?,
+                ext_gs: builder.run(write_graphic_states)?,
+            })
+        })?
+        .phase(|builder| builder.run(write_page_tree))?
+        .phase(|builder| builder.run(write_resource_dictionaries))?
+        .export_with(write_catalog)
+}
+
+/// Settings for PDF export.
+#[derive(Debug, Clone)]
+pub struct PdfOptions<'a> {
+    /// If given, shall be a string that uniquely and stably identifies the
+    /// document. It should not change between compilations of the same
+    /// document. **If you cannot provide such a stable identifier, just pass
+    /// `Smart::Auto` rather than trying to come up with one.** The CLI, for
+    /// example, does not have a well-defined notion of a long-lived project
+    /// and as such just passes `Smart::Auto`.
+    ///
+    /// If an `ident` is given, the hash of it will be used to create a PDF
+    /// document identifier (the identifier itself is not leaked). If `ident`
+    /// is `Auto`, a hash of the document's title and author is used instead
+    /// (which is reasonably unique and stable).
+    pub ident: Smart<&'a str>,
+    /// If given, is expected to be the creation date of the document as a UTC
+    /// datetime. It will only be used if `set document(date: ..)` is `auto`.
+    pub timestamp: Option<Datetime>,
+    /// Specifies which ranges of pages should be exported in the PDF. When
+    /// `None`, all pages should be exported.
+    pub page_ranges: Option<PageRanges>,
+    /// Whether to compress the content streams of pages, patterns and color
+    /// glyphs with the DEFLATE algorithm.
+    ///
+    /// Turning this off makes the raw drawing instructions readable, which is
+    /// useful for debugging. Defaults to `true`.
+    pub compress: bool,
+    /// The PDF version to declare in the file header and metadata.
+    ///
+    /// Defaults to [`PdfVersion::Pdf17`].
+    pub version: PdfVersion,
+    /// Whether to embed an sRGB output intent, which tells viewers and
+    /// printers in which color space the document is meant to be rendered.
+    ///
+    /// The intent has the PDF/A subtype. As its profile is an ICC v4 profile,
+    /// it is valid for PDF/A-2 and later, but not for PDF/A-1. Defaults to
+    /// `false`.
+    pub output_intent: bool,
+    /// Whether viewers should smooth raster images when scaling them up.
+    ///
+    /// Smoothing suits photos, but blurs pixel art and QR codes. Defaults to
+    /// `false`, which leaves the choice to the viewer.
+    pub interpolate_images: bool,
+}
+
+impl Default for PdfOptions<'_> {
+    fn default() -> Self {
+        Self {
+            ident: Smart::Auto,
+            timestamp: None,
+            page_ranges: None,
+            compress: true,
+            version: PdfVersion::default(),
+            output_intent: false,
+            interpolate_images: false,
+        }
+    }
+}
+
+/// A version of the PDF specification.
+///
+/// This only selects the version that the file declares. The exported
+/// content is valid in each of them, as it only requires features of PDF 1.5,
+/// like ICC v4 profiles and images with 16 bits per component.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PdfVersion {
+    /// PDF 1.5.
+    Pdf15,
+    /// PDF 1.6.
+    Pdf16,
+    /// PDF 1.7.
+    #[default]
+    Pdf17,
+}
+
+impl PdfVersion {
+    /// The major and minor version number.
+    pub fn numbers(self) -> (u8, u8) {
+        match self {
+            Self::Pdf15 => (1, 5),
+            Self::Pdf16 => (1, 6),
+            Self::Pdf17 => (1, 7),
+        }
+    }
+
+    /// The version as written in the XMP metadata, e.g. `1.7`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pdf15 => "1.5",
+            Self::Pdf16 => "1.6",
+            Self::Pdf17 => "1.7",
+        }
+    }
 }
 
 /// A struct to build a PDF following a fixed succession of phases.
@@ -124,9 +202,8 @@ struct PdfBuilder<S> {
 struct WithDocument<'a> {
     /// The Typst document that is exported.
     document: &'a Document,
-    /// Page ranges to export.
-    /// When `None`, all pages are exported.
-    exported_pages: Option<PageRanges>,
+    /// Settings for PDF export.
+    options: &'a PdfOptions<'a>,
 }
 
 /// At this point, resources were listed, but they don't have any reference
@@ -135,12 +212,12 @@ struct WithDocument<'a> {
 /// This phase allocates some global references.
 struct WithResources<'a> {
     document: &'a Document,
-    exported_pages: Option<PageRanges>,
+    options: &'a PdfOptions<'a>,
     /// The content of the pages encoded as PDF content streams.
     ///
     /// The pages are at the index corresponding to their page number, but they
     /// may be `None` if they are not in the range specified by
-    /// `exported_pages`.
+    /// `options.page_ranges`.
     pages: Vec<Option<EncodedPage>>,
     /// The PDF resources that are used in the content of the pages.
     resources: Resources<()>,
@@ -170,7 +247,7 @@ impl<'a> From<(WithDocument<'a>, (Vec<Option<EncodedPage>>, Resources<()>))>
     ) -> Self {
         Self {
             document: previous.document,
-            exported_pages: previous.exported_pages,
+            options: previous.options,
             pages,
             resources,
         }
@@ -184,7 +261,7 @@ impl<'a> From<(WithDocument<'a>, (Vec<Option<EncodedPage>>, Resources<()>))>
 /// that will be collected in [`References`].
 struct WithGlobalRefs<'a> {
     document: &'a Document,
-    exported_pages: Option<PageRanges>,
+    options: &'a PdfOptions<'a>,
     pages: Vec<Option<EncodedPage>>,
     /// Resources are the same as in previous phases, but each dictionary now has a reference.
     resources: Resources,
@@ -196,7 +273,7 @@ impl<'a> From<(WithResources<'a>, GlobalRefs)> for WithGlobalRefs<'a> {
     fn from((previous, globals): (WithResources<'a>, GlobalRefs)) -> Self {
         Self {
             document: previous.document,
-            exported_pages: previous.exported_pages,
+            options: previous.options,
             pages: previous.pages,
             resources: previous.resources.with_refs(&globals.resources),
             globals,
@@ -229,7 +306,7 @@ struct WithRefs<'a> {
     globals: GlobalRefs,
     document: &'a Document,
     pages: Vec<Option<EncodedPage>>,
-    exported_pages: Option<PageRanges>,
+    options: &'a PdfOptions<'a>,
     resources: Resources,
     /// References that were allocated for resources.
     references: References,
@@ -239,7 +316,7 @@ impl<'a> From<(WithGlobalRefs<'a>, References)> for WithRefs<'a> {
     fn from((previous, references): (WithGlobalRefs<'a>, References)) -> Self {
         Self {
             globals: previous.globals,
-            exported_pages: previous.exported_pages,
+            options: previous.options,
             document: previous.document,
             pages: previous.pages,
             resources: previous.resources,
@@ -255,7 +332,7 @@ struct WithEverything<'a> {
     globals: GlobalRefs,
     document: &'a Document,
     pages: Vec<Option<EncodedPage>>,
-    exported_pages: Option<PageRanges>,
+    options: &'a PdfOptions<'a>,
     resources: Resources,
     references: References,
     /// Reference that was allocated for the page tree.
@@ -271,7 +348,7 @@ impl<'a> From<(WithEverything<'a>, ())> for WithEverything<'a> {
 impl<'a> From<(WithRefs<'a>, Ref)> for WithEverything<'a> {
     fn from((previous, page_tree_ref): (WithRefs<'a>, Ref)) -> Self {
         Self {
-            exported_pages: previous.exported_pages,
+            options: previous.options,
             globals: previous.globals,
             document: previous.document,
             resources: previous.resources,
@@ -284,42 +361,45 @@ impl<'a> From<(WithRefs<'a>, Ref)> for WithEverything<'a> {
 
 impl<'a> PdfBuilder<WithDocument<'a>> {
     /// Start building a PDF for a Typst document.
-    fn new(document: &'a Document, exported_pages: Option<PageRanges>) -> Self {
+    fn new(document: &'a Document, options: &'a PdfOptions<'a>) -> Self {
+        let mut pdf = Pdf::new();
+        let (major, minor) = options.version.numbers();
+        pdf.set_version(major, minor);
         Self {
             alloc: Ref::new(1),
-            pdf: Pdf::new(),
-            state: WithDocument { document, exported_pages },
+            pdf,
+            state: WithDocument { document, options },
         }
     }
 }
 
 impl<S> PdfBuilder<S> {
     /// Start a new phase, and save its output in the global state.
-    fn phase<NS, B, O>(mut self, builder: B) -> PdfBuilder<NS>
+    fn phase<NS, B, O>(mut self, builder: B) -> SourceResult<PdfBuilder<NS>>
     where
         // New state
         NS: From<(S, O)>,
         // Builder
-        B: Fn(&mut Self) -> O,
+        B: Fn(&mut Self) -> SourceResult<O>,
     {
-        let output = builder(&mut self);
-        PdfBuilder {
+        let output = builder(&mut self)?;
+        Ok(PdfBuilder {
             state: NS::from((self.state, output)),
             alloc: self.alloc,
             pdf: self.pdf,
-        }
+        })
     }
 
     /// Runs a step with the current state, merge its output in the PDF file,
     /// and renumber any references it returned.
-    fn run<P, O>(&mut self, process: P) -> O
+    fn run<P, O>(&mut self, process: P) -> SourceResult<O>
     where
         // Process
-        P: Fn(&S) -> (PdfChunk, O),
+        P: Fn(&S) -> SourceResult<(PdfChunk, O)>,
         // Output
         O: Renumber,
     {
-        let (chunk, mut output) = process(&self.state);
+        let (chunk, mut output) = process(&self.state)?;
         // Allocate a final reference for each temporary one
         let allocated = chunk.alloc.get() - TEMPORARY_REFS_START;
         let offset = TEMPORARY_REFS_START - self.alloc.get();
@@ -336,22 +416,17 @@ impl<S> PdfBuilder<S> {
 
         self.alloc = Ref::new(self.alloc.get() + allocated);
 
-        output
+        Ok(output)
     }
 
     /// Finalize the PDF export and returns the buffer representing the
     /// document.
-    fn export_with<P>(
-        mut self,
-        ident: Smart<&str>,
-        timestamp: Option<Datetime>,
-        process: P,
-    ) -> Vec<u8>
+    fn export_with<P>(mut self, process: P) -> SourceResult<Vec<u8>>
     where
-        P: Fn(S, Smart<&str>, Option<Datetime>, &mut Pdf, &mut Ref),
+        P: Fn(S, &mut Pdf, &mut Ref) -> SourceResult<()>,
     {
-        process(self.state, ident, timestamp, &mut self.pdf, &mut self.alloc);
-        self.pdf.finish()
+        process(self.state, &mut self.pdf, &mut self.alloc)?;
+        Ok(self.pdf.finish())
     }
 }
 
@@ -1,3 +1,4 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 
@@ -6,26 +7,30 @@ use pdf_writer::{
     types::{ActionType, AnnotationFlags, AnnotationType, NumberingStyle},
     Filter, Finish, Name, Rect, Ref, Str,
 };
+use typst::diag::SourceResult;
 use typst::foundations::Label;
 use typst::introspection::Location;
 use typst::layout::{Abs, Frame};
 use typst::model::{Destination, Numbering};
 use typst::text::Case;
 
-use crate::{content, AbsExt, PdfChunk, WithDocument, WithRefs, WithResources};
+use crate::{
+    content, AbsExt, PdfChunk, PdfOptions, WithDocument, WithRefs, WithResources,
+};
 use crate::{font::improve_glyph_sets, Resources};
 
 /// Construct page objects.
 #[typst_macros::time(name = "construct pages")]
 pub fn traverse_pages(
     state: &WithDocument,
-) -> (PdfChunk, (Vec<Option<EncodedPage>>, Resources<()>)) {
+) -> SourceResult<(PdfChunk, (Vec<Option<EncodedPage>>, Resources<()>))> {
     let mut resources = Resources::default();
     let mut pages = Vec::with_capacity(state.document.pages.len());
     let mut skipped_pages = 0;
     for (i, page) in state.document.pages.iter().enumerate() {
         if state
-            .exported_pages
+            .options
+            .page_ranges
             .as_ref()
             .is_some_and(|ranges| !ranges.includes_page_index(i))
         {
@@ -33,7 +38,7 @@ pub fn traverse_pages(
             pages.push(None);
             skipped_pages += 1;
         } else {
-            let mut encoded = construct_page(&mut resources, &page.frame);
+            let mut encoded = construct_page(state.options, &mut resources, &page.frame);
             encoded.label = page
                 .numbering
                 .as_ref()
@@ -55,39 +60,45 @@ pub fn traverse_pages(
     improve_glyph_sets(&mut resources.glyph_sets);
     improve_glyph_sets(&mut resources.color_glyph_sets);
 
-    (PdfChunk::new(), (pages, resources))
+    Ok((PdfChunk::new(), (pages, resources)))
 }
 
 /// Construct a page object.
 #[typst_macros::time(name = "construct page")]
-fn construct_page(out: &mut Resources<()>, frame: &Frame) -> EncodedPage {
-    let content = content::build(out, frame, None);
+fn construct_page(
+    options: &PdfOptions,
+    out: &mut Resources<()>,
+    frame: &Frame,
+) -> EncodedPage {
+    let content = content::build(options, out, frame, None);
 
     EncodedPage { content, label: None }
 }
 
 /// Allocate a reference for each exported page.
-pub fn alloc_page_refs(context: &WithResources) -> (PdfChunk, Vec<Option<Ref>>) {
+pub fn alloc_page_refs(
+    context: &WithResources,
+) -> SourceResult<(PdfChunk, Vec<Option<Ref>>)> {
     let mut chunk = PdfChunk::new();
     let page_refs = context
         .pages
         .iter()
         .map(|p| p.as_ref().map(|_| chunk.alloc()))
         .collect();
-    (chunk, page_refs)
+    Ok((chunk, page_refs))
 }
 
 /// Write the page tree.
-pub fn write_page_tree(ctx: &WithRefs) -> (PdfChunk, Ref) {
+pub fn write_page_tree(ctx: &WithRefs) -> SourceResult<(PdfChunk, Ref)> {
     let mut chunk = PdfChunk::new();
     let page_tree_ref = chunk.alloc.bump();
 
+    let mut contents = HashMap::new();
     for i in 0..ctx.pages.len() {
-        let content_id = chunk.alloc.bump();
         write_page(
             &mut chunk,
             ctx,
-            content_id,
+            &mut contents,
             page_tree_ref,
             &ctx.references.named_destinations.loc_to_dest,
             i,
@@ -99,14 +110,18 @@ pub fn write_page_tree(ctx: &WithRefs) -> (PdfChunk, Ref) {
         .count(ctx.pages.len() as i32)
         .kids(ctx.globals.pages.iter().filter_map(Option::as_ref).copied());
 
-    (chunk, page_tree_ref)
+    Ok((chunk, page_tree_ref))
 }
 
 /// Write a page tree node.
+///
+/// Pages with identical content streams, like blank or template pages, share
+/// a single stream. `contents` maps the hash of each written stream to its
+/// reference.
 fn write_page(
     chunk: &mut PdfChunk,
     ctx: &WithRefs,
-    content_id: Ref,
+    contents: &mut HashMap<u128, Ref>,
     page_tree_ref: Ref,
     loc_to_dest: &HashMap<Location, Label>,
     i: usize,
@@ -164,6 +179,12 @@ fn write_page(
         }
     }
 
+    let data = page.content.content.wait();
+    let (content_id, is_new) = match contents.entry(typst::utils::hash128(data)) {
+        Entry::Occupied(entry) => (*entry.get(), false),
+        Entry::Vacant(entry) => (*entry.insert(chunk.alloc.bump()), true),
+    };
+
     let mut page_writer = chunk.page(page_ref);
     page_writer.parent(page_tree_ref);
 
@@ -187,9 +208,12 @@ fn write_page(
 
     page_writer.finish();
 
-    chunk
-        .stream(content_id, page.content.content.wait())
-        .filter(Filter::FlateDecode);
+    if is_new {
+        let mut stream = chunk.stream(content_id, data);
+        if ctx.options.compress {
+            stream.filter(Filter::FlateDecode);
+        }
+    }
 }
 
 /// Specification for a PDF page label.
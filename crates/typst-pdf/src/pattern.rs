@@ -6,6 +6,7 @@ use pdf_writer::{
     Filter, Name, Rect, Ref,
 };
 
+use typst::diag::SourceResult;
 use typst::layout::{Abs, Ratio, Transform};
 use typst::utils::Numeric;
 use typst::visualize::{Pattern, RelativeTo};
@@ -16,12 +17,14 @@ use crate::{transform_to_array, PdfChunk};
 
 /// Writes the actual patterns (tiling patterns) to the PDF.
 /// This is performed once after writing all pages.
-pub fn write_patterns(context: &WithGlobalRefs) -> (PdfChunk, HashMap<PdfPattern, Ref>) {
+pub fn write_patterns(
+    context: &WithGlobalRefs,
+) -> SourceResult<(PdfChunk, HashMap<PdfPattern, Ref>)> {
     let mut chunk = PdfChunk::new();
     let mut out = HashMap::new();
     context.resources.traverse(&mut |resources| {
         let Some(patterns) = &resources.patterns else {
-            return;
+            return Ok(());
         };
 
         for pdf_pattern in patterns.remapper.items() {
@@ -49,20 +52,21 @@ pub fn write_patterns(context: &WithGlobalRefs) -> (PdfChunk, HashMap<PdfPattern
             // The actual resource dict will be written in a later step
             tiling_pattern.pair(Name(b"Resources"), patterns.resources.reference);
 
-            tiling_pattern
-                .matrix(transform_to_array(
-                    transform
-                        .pre_concat(Transform::scale(Ratio::one(), -Ratio::one()))
-                        .post_concat(Transform::translate(
-                            Abs::zero(),
-                            pattern.spacing().y,
-                        )),
-                ))
-                .filter(Filter::FlateDecode);
+            tiling_pattern.matrix(transform_to_array(
+                transform
+                    .pre_concat(Transform::scale(Ratio::one(), -Ratio::one()))
+                    .post_concat(Transform::translate(Abs::zero(), pattern.spacing().y)),
+            ));
+
+            if context.options.compress {
+                tiling_pattern.filter(Filter::FlateDecode);
+            }
         }
-    });
 
-    (chunk, out)
+        Ok(())
+    })?;
+
+    Ok((chunk, out))
 }
 
 /// A pattern and its transform.
@@ -103,7 +107,8 @@ fn register_pattern(
     };
 
     // Render the body.
-    let content = content::build(&mut patterns.resources, pattern.frame(), None);
+    let content =
+        content::build(ctx.options, &mut patterns.resources, pattern.frame(), None);
 
     let pdf_pattern = PdfPattern {
         transform,
@@ -771,6 +771,7 @@ cast! {
 /// A list of page ranges to be exported. The ranges are one-indexed.
 /// For example, `1..=3` indicates the first, second and third pages should be
 /// exported.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct PageRanges(Vec<PageRange>);
 
 pub type PageRange = RangeInclusive<Option<NonZeroUsize>>;
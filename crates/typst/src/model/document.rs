@@ -36,6 +36,12 @@ pub struct DocumentElem {
     #[ghost]
     pub title: Option<Content>,
 
+    /// The document's description.
+    ///
+    /// Like the title, this is converted to plain text for the PDF metadata.
+    #[ghost]
+    pub description: Option<Content>,
+
     /// The document's authors.
     #[ghost]
     pub author: Author,
@@ -110,6 +116,8 @@ impl Packed<DocumentElem> {
         Ok(Document {
             pages,
             title: DocumentElem::title_in(styles).map(|content| content.plain_text()),
+            description: DocumentElem::description_in(styles)
+                .map(|content| content.plain_text()),
             author: DocumentElem::author_in(styles).0,
             keywords: DocumentElem::keywords_in(styles).0,
             date: DocumentElem::date_in(styles),
@@ -147,6 +155,8 @@ pub struct Document {
     pub pages: Vec<Page>,
     /// The document's title.
     pub title: Option<EcoString>,
+    /// The document's description.
+    pub description: Option<EcoString>,
     /// The document's author.
     pub author: Vec<EcoString>,
     /// The document's keywords.
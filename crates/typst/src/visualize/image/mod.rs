@@ -316,7 +316,7 @@ struct Repr {
 }
 
 /// A kind of image.
-#[derive(Hash)]
+#[derive(Clone, Hash)]
 pub enum ImageKind {
     /// A raster image.
     Raster(RasterImage),
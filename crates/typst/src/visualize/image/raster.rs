@@ -24,6 +24,7 @@ struct Repr {
     dynamic: image::DynamicImage,
     icc: Option<Vec<u8>>,
     dpi: Option<f64>,
+    rotation: Option<u32>,
 }
 
 impl RasterImage {
@@ -53,14 +54,15 @@ impl RasterImage {
             .ok();
 
         // Apply rotation from EXIF metadata.
-        if let Some(rotation) = exif.as_ref().and_then(exif_rotation) {
+        let rotation = exif.as_ref().and_then(exif_rotation);
+        if let Some(rotation) = rotation {
             apply_rotation(&mut dynamic, rotation);
         }
 
         // Extract pixel density.
         let dpi = determine_dpi(&data, exif.as_ref());
 
-        Ok(Self(Arc::new(Repr { data, format, dynamic, icc, dpi })))
+        Ok(Self(Arc::new(Repr { data, format, dynamic, icc, dpi, rotation })))
     }
 
     /// The raw image data.
@@ -97,6 +99,12 @@ impl RasterImage {
     pub fn icc(&self) -> Option<&[u8]> {
         self.0.icc.as_deref()
     }
+
+    /// The EXIF orientation (from 2 to 8) by which the decoded image was
+    /// transformed, such that it no longer matches the raw data, if any.
+    pub fn rotation(&self) -> Option<u32> {
+        self.0.rotation.filter(|rotation| (2..=8).contains(rotation))
+    }
 }
 
 impl Hash for Repr {
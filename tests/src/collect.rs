@@ -25,6 +25,7 @@ pub struct Test {
     pub source: Source,
     pub notes: Vec<Note>,
     pub large: bool,
+    pub pdf: bool,
 }
 
 impl Display for Test {
@@ -253,6 +254,7 @@ impl<'a> Parser<'a> {
 
             let text = self.s.from(start);
             let large = text.starts_with("// LARGE");
+            let pdf = text.starts_with("// PDF");
             if large {
                 self.collector.large.insert(name.clone());
             }
@@ -280,7 +282,9 @@ impl<'a> Parser<'a> {
                 }
             }
 
-            self.collector.tests.push(Test { pos, name, source, notes, large });
+            self.collector
+                .tests
+                .push(Test { pos, name, source, notes, large, pdf });
         }
     }
 
@@ -5,11 +5,12 @@ use std::path::Path;
 use ecow::eco_vec;
 use tiny_skia as sk;
 use typst::diag::{SourceDiagnostic, Warned};
-use typst::foundations::Smart;
+use typst::foundations::{Datetime, Smart};
 use typst::layout::{Abs, Frame, FrameItem, Page, Transform};
 use typst::model::Document;
 use typst::visualize::Color;
 use typst::WorldExt;
+use typst_pdf::PdfOptions;
 
 use crate::collect::{FileSize, NoteKind, Test};
 use crate::world::TestWorld;
@@ -170,11 +171,30 @@ impl<'a> Runner<'a> {
         let data = pixmap_live.encode_png().unwrap();
         std::fs::write(&live_path, data).unwrap();
 
-        // Write PDF if requested.
-        if crate::ARGS.pdf() {
-            let pdf_path = format!("{}/pdf/{}.pdf", crate::STORE_PATH, self.test.name);
-            let pdf = typst_pdf::pdf(document, Smart::Auto, None, None);
-            std::fs::write(pdf_path, pdf).unwrap();
+        // Write PDF if requested. Tests marked with `// PDF` are always
+        // exported to check that the export is deterministic.
+        if crate::ARGS.pdf() || self.test.pdf {
+            let options = PdfOptions {
+                ident: Smart::Custom(self.test.name.as_str()),
+                timestamp: Datetime::from_ymd(2024, 1, 1),
+                ..PdfOptions::default()
+            };
+            let pdf = typst_pdf::pdf(document, &options).unwrap();
+
+            // Exporting the document again with cold caches must produce the
+            // same bytes.
+            if self.test.pdf {
+                comemo::evict(0);
+                if typst_pdf::pdf(document, &options).unwrap() != pdf {
+                    log!(self, "PDF export is not deterministic");
+                }
+            }
+
+            if crate::ARGS.pdf() {
+                let pdf_path =
+                    format!("{}/pdf/{}.pdf", crate::STORE_PATH, self.test.name);
+                std::fs::write(pdf_path, pdf).unwrap();
+            }
         }
 
         // Write SVG if requested.
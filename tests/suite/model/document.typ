@@ -9,6 +9,13 @@ What's up?
 // This, too.
 #set document(author: ("A", "B"), date: datetime.today())
 
+--- document-set-description ---
+#set document(description: [A summary of the document])
+Hello
+
+// Error: 2-36 document set rules must appear before any content
+#set document(description: [Hello])
+
 --- document-date-bad ---
 // Error: 21-28 expected datetime, none, or auto, found string
 #set document(date: "today")
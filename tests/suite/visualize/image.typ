@@ -42,6 +42,7 @@ Stuff
 #image("/assets/images/rhino.png")
 
 --- image-baseline-with-box ---
+// PDF
 // Test baseline.
 A #box(image("/assets/images/tiger.jpg", height: 1cm, width: 80%)) B
 
@@ -50,6 +51,7 @@ A #box(image("/assets/images/tiger.jpg", height: 1cm, width: 80%)) B
 #image("/assets/images/pattern.svg")
 
 --- image-svg-text ---
+// PDF
 #set page(width: 250pt)
 
 #figure(